@@ -31,31 +31,87 @@ pub struct Token {
 	/// end column of the token
 	pub end: uint
 }
+#[deriving(PartialEq,Clone,Eq)]
+/// one of the three bracket shapes a `DelimOpen`/`DelimClose` token can name
+pub enum Delimiter {
+	Parenthesis,
+	Bracket,
+	Brace
+}
+
+impl Show for Delimiter {
+	fn fmt(&self, format: &mut Formatter) -> Result<(), Error> {
+		use Delimiter::*;
+		format.pad(match *self {
+			Parenthesis => "parenthesis",
+			Bracket => "bracket",
+			Brace => "brace"
+		})
+	}
+}
+
 #[deriving(PartialEq,Clone,Eq)]
 pub enum TokenContent {
 	Identifier(String),
 	Lifetime(String),
 	StringLiteral(String),
-	Arrow, // =>
-	Equals, // ==
-	Scope, // ::
-	UnEqual, // !=
+	/// an integer literal, e.g. `1_000`, `0xFF`, `42u64`
+	IntLiteral { value: String, base: u32, suffix: Option<String> },
+	/// a floating-point literal, e.g. `3.14e-2`, `1f32`
+	FloatLiteral { value: String, suffix: Option<String> },
+	/// a raw string literal, e.g. `r"C:\path"` or `r#"has a " in it"#`
+	RawStringLiteral { text: String, hashes: uint },
+	/// a byte string literal, e.g. `b"bytes"`
+	ByteStringLiteral(Vec<u8>),
+	/// a byte literal, e.g. `b'x'`
+	ByteLiteral(u8),
+	/// a raw byte string literal, e.g. `br#"has a " in it"#`
+	RawByteStringLiteral { text: Vec<u8>, hashes: uint },
+	/// a `//` line comment, not including the leading `//`
+	LineComment(String),
+	/// a `/* */` block comment, not including the delimiters
+	BlockComment(String),
+	/// a `///`, `//!`, `/** */` or `/*! */` doc comment; `inner` is true for `//!`/`/*!`
+	DocComment { text: String, inner: bool },
+	DelimOpen(Delimiter),
+	DelimClose(Delimiter),
+	/// any other punctuation/operator token (comparisons, compound assignment, ranges, `->`,
+	/// `::`, `=>`, and the remaining single-character punctuation), holding its exact text,
+	/// e.g. `"<<="` or `".."`; matched by trying the longest known operator first
+	Operator(String),
 	Char(char),
+	/// a leading `#!...` shebang line, not including the `#!` or the trailing newline; only
+	/// ever produced for the very first token of a file
+	Shebang(String),
 	Other(char)
 }
 
-impl Show for TokenContent { 
+impl Show for TokenContent {
 	fn fmt(&self, format: &mut Formatter) -> Result<(), Error> {
 		use TokenContent::*;
 		match *self {
 			Identifier(ref s) => format.pad(s.as_slice()),
 			Lifetime(ref s) => format_args!("'{}", s).fmt(format),
 			StringLiteral(ref s) => format_args!("\"{}\"", s).fmt(format),
-			Arrow => format.pad("'=>'"),
-			Equals => format.pad("'=='"),
-			Scope => format.pad("'::'"),
-			UnEqual => format.pad("'!='"),
+			IntLiteral { ref value, base, ref suffix } => format_args!("{} (base {}){}",
+				value, base, match *suffix { Some(ref s) => format!(" {}", s), None => String::new() }).fmt(format),
+			FloatLiteral { ref value, ref suffix } => format_args!("{}{}",
+				value, match *suffix { Some(ref s) => format!(" {}", s), None => String::new() }).fmt(format),
+			RawStringLiteral { ref text, hashes } => format_args!("r({} hashes) \"{}\"",
+				hashes, text).fmt(format),
+			ByteStringLiteral(ref bytes) => format_args!("b\"{} bytes\"", bytes.len()).fmt(format),
+			ByteLiteral(b) => format_args!("b'{}'", b as char).fmt(format),
+			RawByteStringLiteral { ref text, hashes } => format_args!("br({} hashes) \"{} bytes\"",
+				hashes, text.len()).fmt(format),
+			LineComment(ref s) => format_args!("//{}", s).fmt(format),
+			BlockComment(ref s) => format_args!("/*{}*/", s).fmt(format),
+			DocComment { ref text, inner } => format_args!("{}{}",
+				if inner { "//!" } else { "///" }, text).fmt(format),
+			DelimOpen(ref d) => format_args!("open {}", d).fmt(format),
+			DelimClose(ref d) => format_args!("close {}", d).fmt(format),
+			Operator(ref s) => format_args!("'{}'", s).fmt(format),
 			Char(c) => format_args!("'{}'", c).fmt(format),
+			Shebang(ref s) => format_args!("#!{}", s).fmt(format),
 			Other(c) => format_args!("other: '{}'", c).fmt(format)
 		}
 	}
@@ -74,8 +130,22 @@ impl Show for Token {
 pub struct Lexer<T> {
 	read: T,
 	lookahead: char,
+	/// characters of read-ahead beyond `lookahead`, used to disambiguate constructs that need
+	/// more than one character of foresight (e.g. `1.` vs `1.foo()`, or the maximal-munch
+	/// operator matcher telling `<<=` apart from `<<`)
+	pending: std::collections::RingBuf<char>,
+	/// whether comment tokens (`LineComment`, `BlockComment`, `DocComment`) are yielded
+	/// rather than silently skipped; whitespace is always skipped and never yielded
+	keep_trivia: bool,
 	line: uint,
-	column: uint
+	column: uint,
+	/// tokens already lexed by `peek`/`peek_n` but not yet handed out by `Iterator::next`,
+	/// used to provide lookahead beyond a single token without duplicating
+	/// the character-level `pending` buffer
+	token_buffer: std::collections::RingBuf<IoResult<Token>>,
+	/// whether we are still at byte position zero of the input; shebang recognition is only
+	/// valid there, so this is cleared after the first token is lexed no matter what it was
+	at_start: bool
 }
 
 impl<T: Buffer> Lexer<T> {
@@ -84,15 +154,43 @@ impl<T: Buffer> Lexer<T> {
 		Ok(Lexer {
 			read: read,
 			lookahead: la,
+			pending: std::collections::RingBuf::new(),
+			keep_trivia: false,
 			line: 1,
-			column: 1
+			column: 1,
+			token_buffer: std::collections::RingBuf::new(),
+			at_start: true
 		})
 	}
-	
+
+	/// configures whether comment tokens are yielded instead of skipped; off by default
+	pub fn keep_trivia(mut self, keep_trivia: bool) -> Lexer<T> {
+		self.keep_trivia = keep_trivia;
+		self
+	}
+
+	/// reads the next raw character, consuming the peek buffer first if one is stashed
+	fn raw_read(&mut self) -> IoResult<char> {
+		match self.pending.pop_front() {
+			Some(c) => Ok(c),
+			None => self.read.read_char()
+		}
+	}
+
+	/// looks `n` characters ahead of `lookahead` (`n == 1` is the character immediately
+	/// following `lookahead`) without consuming anything
+	fn peek_at(&mut self, n: uint) -> IoResult<char> {
+		while self.pending.len() < n {
+			let c = try!(self.read.read_char());
+			self.pending.push_back(c);
+		}
+		Ok(self.pending[n - 1])
+	}
+
 	fn skip_whitespace(&mut self) -> IoResult<bool> {
 		while self.lookahead.is_whitespace() {
 			if self.lookahead == '\r' {
-				match self.read.read_char() {
+				match self.raw_read() {
 					Ok(c) => { self.lookahead = c; },
 					Err(ref e) if e.kind == EndOfFile => {
 						self.lookahead = '\0';
@@ -111,7 +209,7 @@ impl<T: Buffer> Lexer<T> {
 			} else {
 				self.column += 1;
 			}
-			match self.read.read_char() {
+			match self.raw_read() {
 				Ok(c) => { self.lookahead = c; },
 				Err(ref e) if e.kind == EndOfFile => {
 					self.lookahead = '\0';
@@ -122,285 +220,941 @@ impl<T: Buffer> Lexer<T> {
 		}
 		Ok(false)
 	}
-	fn parsechar(&mut self) -> IoResult<char> {
-		match self.lookahead {
-			'\\' => {
-				self.lookahead = try!(self.read.read_char());
-				let c = match self.lookahead {
-					'u' => unimplemented!(),
-					'\\' => '\\',
-					'\'' => '\'',
-					'"' => '"',
-					'n' => '\n',
-					't' => '\t',
-					_ => return Err(IoError {
-						kind: std::io::OtherIoError,
-						desc: "unknown escape sequence starting with '{}'",
-						detail: None
-					})
-				};
-				self.lookahead = match self.read.read_char() {
-					Ok(c) => c,
-					Err(ref e) if e.kind == EndOfFile => '\0',
-					Err(e) => return Err(e)
-				};
-				Ok(c)
-			},
-			c @ _ => {
-				self.lookahead = try!(self.read.read_char());
-				Ok(c)
+	/// advances `lookahead` by one character, tracking `\n` line endings the same way
+	/// `skip_whitespace` does (a lone `\r` only advances `column` here); never fails on EOF. Token bodies lexed through `lex_content`
+	/// (block comments, raw strings, ...) can span multiple lines, so this has to track
+	/// newlines the same as whitespace skipping, not just bump `column` unconditionally
+	fn advance(&mut self) -> IoResult<()> {
+		if self.lookahead == '\n' {
+			self.line += 1;
+			self.column = if cfg!(lines_start_at_zero) { 0 } else { 1 };
+		} else {
+			self.column += 1;
+		}
+		match self.raw_read() {
+			Ok(c) => { self.lookahead = c; },
+			Err(ref e) if e.kind == EndOfFile => { self.lookahead = '\0'; },
+			Err(e) => return Err(e)
+		}
+		Ok(())
+	}
+
+	/// makes sure `token_buffer` holds at least `n` tokens (or as many as remain before EOF),
+	/// pulling from `lex_next_token` as needed; an `Err` is itself stashed so it gets handed
+	/// back to the caller exactly once, in order, even if peeked first. End of input is not
+	/// stashed: `lex_next_token` simply reports it again once the buffer is drained
+	fn fill_buffer(&mut self, n: uint) {
+		while self.token_buffer.len() < n {
+			match self.lex_next_token() {
+				Some(tok) => self.token_buffer.push_back(tok),
+				None => break
 			}
 		}
 	}
+
+	/// looks at the next token without consuming it
+	pub fn peek(&mut self) -> Option<&IoResult<Token>> {
+		self.peek_n(0)
+	}
+
+	/// looks `n` tokens ahead (`n == 0` is the same as `peek`) without consuming anything
+	pub fn peek_n(&mut self, n: uint) -> Option<&IoResult<Token>> {
+		self.fill_buffer(n + 1);
+		self.token_buffer.iter().nth(n)
+	}
 }
 
-impl<T: Buffer> Iterator<IoResult<Token>> for Lexer<T> {
-	fn next(&mut self) -> Option<IoResult<Token>> {
+/// the longest operators first, then two-character, then one-character; shared by the
+/// `Lexer`'s maximal-munch matcher and the `&str`-based `advance_token` front end
+static OPERATORS_3: &'static [&'static str] = &["<<=", ">>=", "...", "..="];
+static OPERATORS_2: &'static [&'static str] = &[
+	"->", "=>", "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "::", "..",
+	"+=", "-=", "*=", "/=", "%=", "^=", "&=", "|="];
+static OPERATORS_1: &'static [&'static str] = &[
+	"+", "-", "*", "/", "%", "^", "&", "|", "=", "!", "<", ">",
+	".", ",", ";", ":", "#", "$", "@", "?"];
+
+/// tries to match the longest known operator as a prefix of `window` (which should hold up to
+/// three characters of lookahead)
+fn match_operator(window: &str) -> Option<&'static str> {
+	OPERATORS_3.iter().find(|op| window.starts_with(**op))
+		.or_else(|| OPERATORS_2.iter().find(|op| window.starts_with(**op)))
+		.or_else(|| OPERATORS_1.iter().find(|op| window.starts_with(**op)))
+		.map(|op| *op)
+}
+
+/// adapts a `Lexer<T>`'s character source to the `LexCursor` interface that `lex_content`
+/// and its helpers are written against, reusing the existing `peek_at`/`raw_read` plumbing
+/// for lookahead and column tracking. `lex_content` never needs to abort mid-token, so
+/// instead of threading `IoResult` through every cursor method, any genuine I/O error (as
+/// opposed to a plain end of input) is stashed in `error` and surfaced by `lex_next_token`
+/// once classification of the current token is done.
+struct LexerCursor<'a, T: 'a> {
+	lexer: &'a mut Lexer<T>,
+	error: Option<IoError>
+}
+
+impl<'a, T: Buffer> LexCursor for LexerCursor<'a, T> {
+	fn nth(&mut self, n: uint) -> char {
+		if n == 0 {
+			return self.lexer.lookahead;
+		}
+		match self.lexer.peek_at(n) {
+			Ok(c) => c,
+			Err(ref e) if e.kind == EndOfFile => '\0',
+			Err(e) => {
+				if self.error.is_none() { self.error = Some(e); }
+				'\0'
+			}
+		}
+	}
+	fn first(&mut self) -> char { self.lexer.lookahead }
+	fn second(&mut self) -> char { self.nth(1) }
+	fn is_eof(&mut self) -> bool { self.lexer.lookahead == '\0' }
+	fn bump(&mut self) -> Option<char> {
+		if self.lexer.lookahead == '\0' {
+			return None;
+		}
+		let c = self.lexer.lookahead;
+		if let Err(e) = self.lexer.advance() {
+			if self.error.is_none() { self.error = Some(e); }
+		}
+		Some(c)
+	}
+}
+
+/// converts a recoverable `TokenError` from the shared `lex_content` core into the hard
+/// `IoError` the IO-backed `Lexer` aborts the token stream with. The coarser `TokenError`
+/// enum can't carry the same level of detail the old hand-written `Lexer` parser used to
+/// report, so these descriptions are necessarily more general.
+fn token_error_to_io_error(error: TokenError) -> IoError {
+	match error {
+		TokenError::UnterminatedString => IoError {
+			kind: EndOfFile,
+			desc: "End of file or missing closing quote while reading a string, character or byte literal",
+			detail: None
+		},
+		TokenError::UnterminatedRawString => IoError {
+			kind: EndOfFile,
+			desc: "End of file while reading a raw string literal",
+			detail: None
+		},
+		TokenError::UnterminatedBlockComment => IoError {
+			kind: EndOfFile,
+			desc: "End of file while reading a block comment",
+			detail: None
+		},
+		TokenError::UnknownEscape => IoError {
+			kind: std::io::OtherIoError,
+			desc: "unknown or out-of-range escape sequence, or non-ASCII character in a byte literal",
+			detail: None
+		},
+		TokenError::BadUnicodeEscape => IoError {
+			kind: std::io::OtherIoError,
+			desc: "'\\u{...}' escape is malformed or not a valid unicode scalar value",
+			detail: None
+		}
+	}
+}
+
+impl<T: Buffer> Lexer<T> {
+	/// does the actual work of lexing the next token, bypassing `token_buffer`; `Iterator::next`
+	/// and `fill_buffer` are the only callers, since both need to distinguish "freshly lexed"
+	/// from "already stashed by a peek" tokens. Shebang recognition and whitespace skipping
+	/// happen here, but the per-token classification itself is delegated to `lex_content`
+	/// through a `LexerCursor` adapter, so it is written once and shared with `advance_token`.
+	fn lex_next_token(&mut self) -> Option<IoResult<Token>> {
 		use TokenContent::*;
 		macro_rules! proceed(
 			()=>({
-				self.column += 1;
-				match self.read.read_char() {
-					Ok(c) => { self.lookahead = c; },
-					Err(ref e) if e.kind == EndOfFile => { self.lookahead = '\0'; },
+				match self.advance() {
+					Ok(()) => {},
 					Err(e) => return Some(Err(e))
 				}
 			}));
 		if self.lookahead == '\0' {
 			return None;
 		}
+		if self.at_start {
+			self.at_start = false;
+			let is_shebang = self.lookahead == '#' && match self.peek_at(1) {
+				Ok('!') => match self.peek_at(2) {
+					Ok('[') => false,
+					Ok(_) => true,
+					Err(ref e) if e.kind == EndOfFile => true,
+					Err(e) => return Some(Err(e))
+				},
+				Ok(_) => false,
+				Err(ref e) if e.kind == EndOfFile => false,
+				Err(e) => return Some(Err(e))
+			};
+			if is_shebang {
+				let line = self.line;
+				let col = self.column;
+				proceed!(); // '#'
+				proceed!(); // '!'
+				let mut text = Vec::new();
+				while self.lookahead != '\n' && self.lookahead != '\0' {
+					text.push(self.lookahead);
+					proceed!();
+				}
+				return Some(Ok(Token {
+					content: Shebang(text.into_iter().collect()),
+					line: line,
+					start: col,
+					end: self.column
+				}));
+			}
+		}
 		match self.skip_whitespace() {
 			Ok(false) => {},
 			Ok(true) => return None,
 			Err(e) => return Some(Err(e))
 		}
-		match self.lookahead {
-			'=' => {
-				let col = self.column;
-				proceed!();
-				if self.lookahead == '>' {
-					proceed!();
-					Some(Ok(Token {
-						content: Arrow,
-						line: self.line,
-						start: col,
-						end: self.column
-					}))
-				} else if self.lookahead == '=' {
-					proceed!();
-					Some(Ok(Token {
-						content: Equals,
-						line: self.line,
-						start: col,
-						end: self.column
-					}))
-				} else {
-					Some(Ok(Token {
-						content: Other('='),
-						line: self.line,
-						start: col,
-						end: self.column
-					}))
-				}
-			},
-			':' => {
-				let col = self.column;
-				proceed!();
-				if self.lookahead == ':' {
-					proceed!();
-					Some(Ok(Token {
-						content: Scope,
-						line: self.line,
-						start: col,
-						end: self.column
-					}))
-				} else {
-					Some(Ok(Token {
-						content: Other(':'),
-						line: self.line,
-						start: col,
-						end: self.column
-					}))
-				}
-			},
-			'!' => {
-				let col = self.column;
-				proceed!();
-				if self.lookahead == '=' {
-					proceed!();
-					Some(Ok(Token {
-						content: UnEqual,
-						line: self.line,
-						start: col,
-						end: self.column
-					}))
-				} else {
-					Some(Ok(Token {
-						content: Other('!'),
-						line: self.line,
-						start: col,
-						end: self.column
-					}))
-				}
-			},
-			'/' => {
-				let col = self.column;
-				proceed!();
-				match self.lookahead {
-					'/' => {
-						proceed!();
-						while self.lookahead != '\n' {
-							proceed!();
-						}
-						self.next()
-					},
-					_ => Some(Ok(Token {
-						content: Other('/'),
-						line: self.line,
-						start: col,
-						end: self.column
-					}))
+		let line = self.line;
+		let col = self.column;
+		let (content, token_error, io_error) = {
+			let mut cursor = LexerCursor { lexer: &mut *self, error: None };
+			let (content, token_error) = lex_content(&mut cursor);
+			(content, token_error, cursor.error)
+		};
+		if let Some(e) = io_error {
+			return Some(Err(e));
+		}
+		if let Some(e) = token_error {
+			return Some(Err(token_error_to_io_error(e)));
+		}
+		match content {
+			LineComment(_) | BlockComment(_) | DocComment { .. } if !self.keep_trivia =>
+				self.lex_next_token(),
+			_ => Some(Ok(Token { content: content, line: line, start: col, end: self.column }))
+		}
+	}
+}
+
+impl<T: Buffer> Iterator<IoResult<Token>> for Lexer<T> {
+	fn next(&mut self) -> Option<IoResult<Token>> {
+		match self.token_buffer.pop_front() {
+			Some(tok) => Some(tok),
+			None => self.lex_next_token()
+		}
+	}
+}
+
+/// a recoverable lexing problem attached to a `RawToken` instead of aborting the stream; this
+/// is what lets `advance_token` keep producing tokens through malformed input, which matters
+/// for editors and other tooling that must tolerate partial/invalid source
+#[deriving(PartialEq,Clone,Eq)]
+pub enum TokenError {
+	/// a `"..."`, `'...'`, `b"..."` or `b'...'` literal that ran off the end of the input, or a
+	/// `'...'`/`b'...'` literal whose closing `'` does not follow its single character
+	UnterminatedString,
+	/// a raw string/byte-string literal whose `hashes`-many-`#` closing delimiter was never found
+	UnterminatedRawString,
+	/// a `/* ... */` comment that ran off the end of the input before its matching `*/`
+	UnterminatedBlockComment,
+	/// an escape sequence starting with an unrecognized character after `\`, a `\x` escape
+	/// without two hex digits or out of range, or a non-ASCII character in a byte, byte string
+	/// or raw byte string literal
+	UnknownEscape,
+	/// a `\u{...}` escape that is malformed or not a valid unicode scalar value
+	BadUnicodeEscape
+}
+
+/// a token produced by the `&str`-based front end. Unlike `Token`, this never fails to
+/// produce a result for malformed input: recoverable problems are attached via `error`
+/// and lexing continues with the next token rather than aborting the whole stream.
+#[deriving(PartialEq,Clone)]
+pub struct RawToken {
+	pub content: TokenContent,
+	/// length of the token in bytes of the source `&str`, including any skipped leading whitespace
+	pub len: uint,
+	pub error: Option<TokenError>
+}
+
+/// the character-level operations that `lex_content` and its helpers need from whatever is
+/// backing the token stream; implemented once for the cheap `&str`-backed `Cursor` used by
+/// `advance_token`, and once for the IO-backed `Lexer`, so the per-token classification in
+/// `lex_content` is written exactly once and shared by both front ends. `'\0'` signals
+/// exhausted input everywhere, matching the sentinel the IO-backed `Lexer` already used for
+/// its `lookahead` field before this trait existed.
+trait LexCursor {
+	/// the character at the cursor, or `'\0'` once input is exhausted
+	fn first(&mut self) -> char;
+	/// the character after `first()`, or `'\0'` once input is exhausted
+	fn second(&mut self) -> char;
+	/// the character `n` positions ahead (`n == 0` is `first()`), or `'\0'` once input is exhausted
+	fn nth(&mut self, n: uint) -> char;
+	/// consumes and returns the character at the cursor, or `None` once input is exhausted
+	fn bump(&mut self) -> Option<char>;
+	fn is_eof(&mut self) -> bool;
+}
+
+/// a cheap cursor over the remaining input, whose `Chars` is cloned to look ahead; byte offsets are derived by comparing
+/// the length of the remaining `&str` before and after lexing a token, rather than tracked
+/// incrementally, which is what makes the cursor cheap to fork for lookahead
+struct Cursor<'a> {
+	chars: std::str::Chars<'a>
+}
+
+impl<'a> Cursor<'a> {
+	fn rest_len(&self) -> uint { self.chars.as_str().len() }
+}
+
+impl<'a> LexCursor for Cursor<'a> {
+	fn nth(&mut self, n: uint) -> char {
+		let mut it = self.chars.clone();
+		for _ in range(0u, n) { it.next(); }
+		it.next().unwrap_or('\0')
+	}
+	fn first(&mut self) -> char { self.nth(0) }
+	fn second(&mut self) -> char { self.nth(1) }
+	fn is_eof(&mut self) -> bool { self.chars.as_str().is_empty() }
+	fn bump(&mut self) -> Option<char> { self.chars.next() }
+}
+
+/// parses one escape sequence, returning the character it resolves to and an error flag
+/// instead of failing outright when the escape is malformed. `byte_mode` relaxes the `\x`
+/// escape to the full `0x00`-`0xFF` range, matching the rules for byte char/string literals
+/// rather than `char`/`str` ones. Returns `None` (no error) for a `\`-newline line
+/// continuation, which contributes no character of its own; callers should go through
+/// `lex_one_char` rather than calling this directly so that case is handled for them.
+fn lex_escape<C: LexCursor>(cursor: &mut C, byte_mode: bool) -> (Option<char>, Option<TokenError>) {
+	cursor.bump(); // the '\\'
+	match cursor.first() {
+		'\\' => { cursor.bump(); (Some('\\'), None) },
+		'\'' => { cursor.bump(); (Some('\''), None) },
+		'"' => { cursor.bump(); (Some('"'), None) },
+		'n' => { cursor.bump(); (Some('\n'), None) },
+		'r' => { cursor.bump(); (Some('\r'), None) },
+		't' => { cursor.bump(); (Some('\t'), None) },
+		'0' => { cursor.bump(); (Some('\0'), None) },
+		'x' => {
+			cursor.bump();
+			let mut value = 0u32;
+			let mut ok = true;
+			for _ in range(0u, 2u) {
+				match cursor.first().to_digit(16) {
+					Some(d) => { value = value * 16 + d as u32; cursor.bump(); },
+					None => { ok = false; }
 				}
 			}
-			'\'' => {
-				let col = self.column;
-				proceed!();
-				if self.lookahead.is_xid_start() || self.lookahead == '_' {
-					let tok = match self.next() {
-						Some(Ok(x)) => x,
-						Some(Err(e)) => return Some(Err(e)),
-						None => return Some(Err(IoError {
-							kind: EndOfFile,
-							desc: "End of file while reading Character literal",
-							detail: None
-						}))
-					};
-					match tok.content {
-						Identifier(id) => if id.as_slice().chars().count() > 1
-							|| self.lookahead != '\'' { //TODO: better solution for chars().count() > 1
-							Some(Ok(Token {
-								content: Lifetime(id),
-								line: self.line,
-								start: col,
-								end: self.column
-							}))
-						} else {
-							proceed!();
-							Some(Ok(Token {
-								content: Char(id.as_slice().char_at(0)),
-								line: self.line,
-								start: col,
-								end: self.column
-							}))
-						},
-						_ => panic!()
-					}
-				} else {
-					let col = self.column;
-					let line = self.line;
-					let c = match self.parsechar() {
-						Ok(c) => c,
-						Err(e) => return Some(Err(e))
-					};
-					match self.lookahead {
-						'\0' => Some(Err(IoError {
-							kind: EndOfFile,
-							desc: "End of file while reading character literal",
-							detail: None
-						})),
-						'\'' => {
-							let end = self.column;
-							proceed!();
-							Some(Ok(Token {
-								content: Char(c),
-								line: line,
-								start: col,
-								end: end
-							}))
-						},
-						_ => Some(Err(IoError {
-							kind: std::io::OtherIoError,
-							desc: "unclosed character literal",
-							detail: None
-						}))
-					}
-				}
-			},
-			'"' => {
-				let start_line = self.line;
-				let col = self.column;
-				proceed!();
-				let mut text: Vec<char> = Vec::new();
-				while self.lookahead != '"' && self.lookahead != '\0' {
-					text.push(match self.parsechar() {
-						Ok(c) => c,
-						Err(e) => return Some(Err(e))
-					});
-				}
-				if self.lookahead == '\0' {
-					return Some(Err(IoError {
-						kind: EndOfFile,
-						desc: "End of file while reading string literal",
-						detail: None
-					}));
-				}
-				proceed!();
-				Some(Ok(Token {
-					content: StringLiteral(text.into_iter().collect()),
-					line: start_line,
-					start: col,
-					end: self.column
-				}))
-			},
-			_ if self.lookahead.is_xid_start() || self.lookahead == '_' => {
-				let start = self.column;
-				let mut id: Vec<char> = Vec::with_capacity(16);
-				id.push(self.lookahead);
-				'a: loop {
-					match self.read.read_char() {
-						Ok(c) => {
-							self.column += 1;
-							if c.is_xid_continue() {
-								id.push(c);
-							} else {
-								self.lookahead = c;
-								break 'a;
-							}
-						},
-						Err(ref e) if e.kind == EndOfFile => {
-							self.lookahead = '\0';
-							break 'a;
-						},
-						Err(e) => return Some(Err(e))
-					}
+			if !ok || (!byte_mode && value > 0x7F) {
+				(Some('\u{FFFD}'), Some(TokenError::UnknownEscape))
+			} else {
+				(Some(value as u8 as char), None)
+			}
+		},
+		'u' => {
+			cursor.bump();
+			if cursor.first() != '{' {
+				return (Some('\u{FFFD}'), Some(TokenError::BadUnicodeEscape));
+			}
+			cursor.bump();
+			let mut value = 0u32;
+			let mut digits = 0u;
+			while cursor.first() != '}' && !cursor.is_eof() && digits < 6 {
+				match cursor.first().to_digit(16) {
+					Some(d) => { value = value * 16 + d as u32; digits += 1; cursor.bump(); },
+					None => break
 				}
-				let str_ = id.into_iter().collect();
-				Some(Ok(Token {
-					content: Identifier(str_),
-					line: self.line,
-					start: start,
-					end: self.column
-				}))
-			},
-			c => {
-				match self.read.read_char() {
-					Ok(c) => { self.lookahead = c; },
-					Err(ref e) if e.kind == EndOfFile => { self.lookahead = '\0'; },
-					Err(e) => return Some(Err(e))
-				};
-				Some(Ok(Token {
-					content: Other(c),
-					line: self.line,
-					start: self.column,
-					end: self.column + 1
-				}))
 			}
+			let closed = cursor.first() == '}';
+			if closed { cursor.bump(); }
+			match char::from_u32(value) {
+				Some(c) if digits > 0 && closed => (Some(c), None),
+				_ => (Some('\u{FFFD}'), Some(TokenError::BadUnicodeEscape))
+			}
+		},
+		'\n' => {
+			// line continuation: the backslash-newline and any leading whitespace on the
+			// following line are swallowed entirely
+			cursor.bump();
+			while cursor.first() == ' ' || cursor.first() == '\t' {
+				cursor.bump();
+			}
+			(None, None)
+		},
+		_ => {
+			cursor.bump();
+			(Some('\u{FFFD}'), Some(TokenError::UnknownEscape))
+		}
+	}
+}
+
+/// parses one logical character starting at `cursor`, resolving an escape sequence (and any
+/// number of line continuations, which contribute no character of their own); shared by every
+/// string/char/byte literal parser below, and by both the IO-backed and `&str`-backed front ends
+fn lex_one_char<C: LexCursor>(cursor: &mut C, byte_mode: bool) -> (char, Option<TokenError>) {
+	loop {
+		if cursor.is_eof() {
+			return ('\0', Some(TokenError::UnterminatedString));
+		}
+		if cursor.first() != '\\' {
+			// byte/byte-string literals are only ever ASCII; a non-ASCII char here would
+			// silently truncate to a meaningless byte under the `as u8` cast at the call site,
+			// so flag it instead of bumping past it unchecked
+			if byte_mode && cursor.first() as u32 > 0x7F {
+				cursor.bump();
+				return ('\u{FFFD}', Some(TokenError::UnknownEscape));
+			}
+			return (cursor.bump().unwrap(), None);
+		}
+		match lex_escape(cursor, byte_mode) {
+			(Some(c), error) => return (c, error),
+			(None, _) => continue
+		}
+	}
+}
+
+fn lex_number<C: LexCursor>(cursor: &mut C) -> TokenContent {
+	let mut base = 10u32;
+	let mut value = String::new();
+	if cursor.first() == '0' {
+		cursor.bump();
+		match cursor.first() {
+			'x' | 'X' => { base = 16; cursor.bump(); },
+			'o' | 'O' => { base = 8; cursor.bump(); },
+			'b' | 'B' => { base = 2; cursor.bump(); },
+			_ => value.push('0')
 		}
 	}
+	while cursor.first().is_digit(base) || cursor.first() == '_' {
+		let c = cursor.bump().unwrap();
+		if c != '_' { value.push(c); }
+	}
+	let mut is_float = false;
+	if base == 10 && cursor.first() == '.' && cursor.second().is_digit(10) {
+		is_float = true;
+		value.push(cursor.bump().unwrap());
+		while cursor.first().is_digit(10) || cursor.first() == '_' {
+			let c = cursor.bump().unwrap();
+			if c != '_' { value.push(c); }
+		}
+	}
+	if base == 10 && (cursor.first() == 'e' || cursor.first() == 'E')
+		&& (cursor.second().is_digit(10) || cursor.second() == '+' || cursor.second() == '-') {
+		is_float = true;
+		value.push(cursor.bump().unwrap());
+		if cursor.first() == '+' || cursor.first() == '-' {
+			value.push(cursor.bump().unwrap());
+		}
+		while cursor.first().is_digit(10) || cursor.first() == '_' {
+			let c = cursor.bump().unwrap();
+			if c != '_' { value.push(c); }
+		}
+	}
+	let mut suffix = String::new();
+	if cursor.first().is_xid_start() || cursor.first() == '_' {
+		suffix.push(cursor.bump().unwrap());
+		while cursor.first().is_xid_continue() {
+			suffix.push(cursor.bump().unwrap());
+		}
+	}
+	let suffix = if suffix.is_empty() { None } else { Some(suffix) };
+	if let Some(ref s) = suffix {
+		if s.as_slice() == "f32" || s.as_slice() == "f64" { is_float = true; }
+	}
+	if is_float {
+		TokenContent::FloatLiteral { value: value, suffix: suffix }
+	} else {
+		TokenContent::IntLiteral { value: value, base: base, suffix: suffix }
+	}
+}
+
+fn lex_ident<C: LexCursor>(cursor: &mut C) -> TokenContent {
+	let mut id = String::new();
+	id.push(cursor.bump().unwrap());
+	while cursor.first().is_xid_continue() {
+		id.push(cursor.bump().unwrap());
+	}
+	TokenContent::Identifier(id)
+}
+
+fn lex_char_or_lifetime<C: LexCursor>(cursor: &mut C) -> (TokenContent, Option<TokenError>) {
+	cursor.bump(); // opening '\''
+	if cursor.first().is_xid_start() || cursor.first() == '_' {
+		let mut id = String::new();
+		id.push(cursor.bump().unwrap());
+		while cursor.first().is_xid_continue() {
+			id.push(cursor.bump().unwrap());
+		}
+		if id.as_slice().chars().count() > 1 || cursor.first() != '\'' {
+			return (TokenContent::Lifetime(id), None);
+		}
+		cursor.bump(); // closing '\''
+		return (TokenContent::Char(id.as_slice().char_at(0)), None);
+	}
+	let (c, error) = lex_one_char(cursor, false);
+	if cursor.first() == '\'' {
+		cursor.bump();
+		(TokenContent::Char(c), error)
+	} else {
+		(TokenContent::Char(c), Some(error.unwrap_or(TokenError::UnterminatedString)))
+	}
+}
+
+fn lex_string<C: LexCursor>(cursor: &mut C) -> (TokenContent, Option<TokenError>) {
+	cursor.bump(); // opening '"'
+	let mut text = String::new();
+	let mut error = None;
+	loop {
+		if cursor.is_eof() {
+			error = Some(TokenError::UnterminatedString);
+			break;
+		}
+		if cursor.first() == '"' {
+			cursor.bump();
+			break;
+		}
+		let (c, e) = lex_one_char(cursor, false);
+		if error.is_none() { error = e; }
+		text.push(c);
+	}
+	(TokenContent::StringLiteral(text), error)
+}
+
+fn lex_byte_string<C: LexCursor>(cursor: &mut C) -> (TokenContent, Option<TokenError>) {
+	cursor.bump(); // 'b'
+	cursor.bump(); // '"'
+	let mut bytes: Vec<u8> = Vec::new();
+	let mut error = None;
+	loop {
+		if cursor.is_eof() {
+			error = Some(TokenError::UnterminatedString);
+			break;
+		}
+		if cursor.first() == '"' {
+			cursor.bump();
+			break;
+		}
+		let (c, e) = lex_one_char(cursor, true);
+		if error.is_none() { error = e; }
+		bytes.push(c as u8);
+	}
+	(TokenContent::ByteStringLiteral(bytes), error)
+}
+
+fn lex_byte_char<C: LexCursor>(cursor: &mut C) -> (TokenContent, Option<TokenError>) {
+	cursor.bump(); // 'b'
+	cursor.bump(); // '\''
+	let (c, error) = lex_one_char(cursor, true);
+	if cursor.first() == '\'' {
+		cursor.bump();
+		(TokenContent::ByteLiteral(c as u8), error)
+	} else {
+		(TokenContent::ByteLiteral(c as u8), Some(error.unwrap_or(TokenError::UnterminatedString)))
+	}
+}
+
+fn lex_raw_string<C: LexCursor>(cursor: &mut C, is_byte: bool) -> (TokenContent, Option<TokenError>) {
+	cursor.bump(); // 'r' or 'b'
+	if is_byte { cursor.bump(); } // the 'r' of `br`
+	let mut hashes = 0u;
+	while cursor.first() == '#' {
+		hashes += 1;
+		cursor.bump();
+	}
+	cursor.bump(); // opening '"'
+	// raw byte strings are only ever ASCII, so each ASCII char is pushed as one byte, rather
+	// than collected as a `String` and converted with `into_bytes()`, which would keep the
+	// multi-byte UTF-8 encoding of any non-ASCII char; a non-ASCII char is flagged and skipped
+	let mut text = String::new();
+	let mut bytes: Vec<u8> = Vec::new();
+	let mut error = None;
+	loop {
+		if cursor.is_eof() {
+			error = Some(TokenError::UnterminatedRawString);
+			break;
+		}
+		if cursor.first() == '"' {
+			cursor.bump();
+			let mut trailing = 0u;
+			while trailing < hashes && cursor.first() == '#' {
+				trailing += 1;
+				cursor.bump();
+			}
+			if trailing == hashes {
+				break;
+			}
+			if is_byte {
+				bytes.push('"' as u8);
+				for _ in range(0u, trailing) { bytes.push('#' as u8); }
+			} else {
+				text.push('"');
+				for _ in range(0u, trailing) { text.push('#'); }
+			}
+			continue;
+		}
+		if is_byte && cursor.first() as u32 > 0x7F {
+			if error.is_none() { error = Some(TokenError::UnknownEscape); }
+			cursor.bump();
+			continue;
+		}
+		let c = cursor.bump().unwrap();
+		if is_byte { bytes.push(c as u8); } else { text.push(c); }
+	}
+	let content = if is_byte {
+		TokenContent::RawByteStringLiteral { text: bytes, hashes: hashes }
+	} else {
+		TokenContent::RawStringLiteral { text: text, hashes: hashes }
+	};
+	(content, error)
+}
+
+fn lex_line_comment<C: LexCursor>(cursor: &mut C) -> TokenContent {
+	cursor.bump(); cursor.bump(); // "//"
+	let (is_doc, inner) = match cursor.first() {
+		'!' => { cursor.bump(); (true, true) },
+		'/' if cursor.second() != '/' => { cursor.bump(); (true, false) },
+		_ => (false, false)
+	};
+	let mut text = String::new();
+	while !cursor.is_eof() && cursor.first() != '\n' {
+		text.push(cursor.bump().unwrap());
+	}
+	if is_doc {
+		TokenContent::DocComment { text: text, inner: inner }
+	} else {
+		TokenContent::LineComment(text)
+	}
+}
+
+fn lex_block_comment<C: LexCursor>(cursor: &mut C) -> (TokenContent, Option<TokenError>) {
+	cursor.bump(); cursor.bump(); // "/*"
+	let is_doc_outer = cursor.first() == '*' && cursor.second() != '/';
+	let is_doc_inner = cursor.first() == '!';
+	if is_doc_outer || is_doc_inner {
+		cursor.bump();
+	}
+	let mut text = String::new();
+	let mut depth = 1u;
+	let mut error = None;
+	loop {
+		if cursor.is_eof() {
+			error = Some(TokenError::UnterminatedBlockComment);
+			break;
+		}
+		if cursor.first() == '/' && cursor.second() == '*' {
+			text.push(cursor.bump().unwrap());
+			text.push(cursor.bump().unwrap());
+			depth += 1;
+			continue;
+		}
+		if cursor.first() == '*' && cursor.second() == '/' {
+			cursor.bump();
+			cursor.bump();
+			depth -= 1;
+			if depth == 0 {
+				break;
+			}
+			text.push('*');
+			text.push('/');
+			continue;
+		}
+		text.push(cursor.bump().unwrap());
+	}
+	let content = if is_doc_outer {
+		TokenContent::DocComment { text: text, inner: false }
+	} else if is_doc_inner {
+		TokenContent::DocComment { text: text, inner: true }
+	} else {
+		TokenContent::BlockComment(text)
+	};
+	(content, error)
+}
+
+fn lex_content<C: LexCursor>(cursor: &mut C) -> (TokenContent, Option<TokenError>) {
+	use TokenContent::*;
+	match cursor.first() {
+		'/' if cursor.second() == '/' => (lex_line_comment(cursor), None),
+		'/' if cursor.second() == '*' => lex_block_comment(cursor),
+		'"' => lex_string(cursor),
+		'\'' => lex_char_or_lifetime(cursor),
+		'r' if cursor.second() == '"' || cursor.second() == '#' => lex_raw_string(cursor, false),
+		'b' if cursor.second() == '\'' => lex_byte_char(cursor),
+		'b' if cursor.second() == '"' => lex_byte_string(cursor),
+		'b' if cursor.second() == 'r' && (cursor.nth(2) == '"' || cursor.nth(2) == '#') =>
+			lex_raw_string(cursor, true),
+		c if c.is_digit(10) => (lex_number(cursor), None),
+		c if c.is_xid_start() || c == '_' => (lex_ident(cursor), None),
+		'(' => { cursor.bump(); (DelimOpen(Delimiter::Parenthesis), None) },
+		')' => { cursor.bump(); (DelimClose(Delimiter::Parenthesis), None) },
+		'[' => { cursor.bump(); (DelimOpen(Delimiter::Bracket), None) },
+		']' => { cursor.bump(); (DelimClose(Delimiter::Bracket), None) },
+		'{' => { cursor.bump(); (DelimOpen(Delimiter::Brace), None) },
+		'}' => { cursor.bump(); (DelimClose(Delimiter::Brace), None) },
+		_ => {
+			let window: String = vec!(cursor.first(), cursor.second(), cursor.nth(2)).into_iter().collect();
+			match match_operator(window.as_slice()) {
+				Some(op) => {
+					for _ in range(0u, op.len()) { cursor.bump(); }
+					(Operator(op.to_string()), None)
+				},
+				None => (Other(cursor.bump().unwrap()), None)
+			}
+		}
+	}
+}
+
+/// lexes a single token (including any leading whitespace) from `chars`, advancing it past
+/// what was consumed. Returns `None` once only whitespace remains. Never fails: malformed
+/// input is reported through `RawToken::error` so the caller can keep asking for more tokens,
+/// which is what makes this suitable for editors and other tools that must tolerate invalid
+/// or partial source.
+pub fn advance_token(chars: &mut std::str::Chars) -> Option<RawToken> {
+	let start_len = chars.as_str().len();
+	let mut cursor = Cursor { chars: chars.clone() };
+	while !cursor.is_eof() && cursor.first().is_whitespace() {
+		cursor.bump();
+	}
+	if cursor.is_eof() {
+		*chars = cursor.chars;
+		return None;
+	}
+	let (content, error) = lex_content(&mut cursor);
+	let len = start_len - cursor.rest_len();
+	*chars = cursor.chars;
+	Some(RawToken { content: content, len: len, error: error })
+}
+
+/// drives `advance_token` over a whole `&str` as an `Iterator`, for callers (syntax
+/// highlighters, IDE features) that want a token stream rather than one-shot calls
+pub struct StrLexer<'a> {
+	chars: std::str::Chars<'a>
+}
+
+impl<'a> StrLexer<'a> {
+	pub fn new(input: &'a str) -> StrLexer<'a> {
+		StrLexer { chars: input.chars() }
+	}
+}
+
+impl<'a> Iterator<RawToken> for StrLexer<'a> {
+	fn next(&mut self) -> Option<RawToken> {
+		advance_token(&mut self.chars)
+	}
+}
+
+/// lexes all of `s` with the `&str`-based front end and returns just the `TokenContent`s,
+/// for tests that only care about how something is classified, not its span
+#[cfg(test)]
+fn lex_str_contents(s: &str) -> Vec<TokenContent> {
+	StrLexer::new(s).map(|t| t.content).collect()
+}
+
+#[test]
+fn lex_shebang() {
+	use TokenContent::*;
+	let mut lex = match Lexer::new(
+		std::io::BufferedReader::new(
+			std::io::MemReader::new(std::vec::as_vec(b"#!/usr/bin/env rustc\nfn").clone()))) {
+		Ok(x) => x,
+		Err(e) => panic!("{}", e)
+	};
+	assert_eq!(lex.next().unwrap().ok().unwrap().content, Shebang("/usr/bin/env rustc".to_string()));
+	assert_eq!(lex.next().unwrap().ok().unwrap().content, Identifier("fn".to_string()));
+	// `#!` is only a shebang at the very start of the input; a top-level inner attribute
+	// like `#![...]` must not be swallowed as one, and `#!` anywhere but position zero is
+	// just the `#` and `!` operators
+	let mut lex = match Lexer::new(
+		std::io::BufferedReader::new(
+			std::io::MemReader::new(std::vec::as_vec(b"#![feature(foo)]").clone()))) {
+		Ok(x) => x,
+		Err(e) => panic!("{}", e)
+	};
+	assert_eq!(lex.next().unwrap().ok().unwrap().content, Operator("#".to_string()));
+	assert_eq!(lex.next().unwrap().ok().unwrap().content, Operator("!".to_string()));
+}
+
+#[test]
+fn lex_peek() {
+	use TokenContent::*;
+	let mut lex = match Lexer::new(
+		std::io::BufferedReader::new(
+			std::io::MemReader::new(std::vec::as_vec(b"a b c").clone()))) {
+		Ok(x) => x,
+		Err(e) => panic!("{}", e)
+	};
+	// peeking must not consume: repeated peeks at the same depth see the same token
+	assert_eq!(lex.peek().unwrap().as_ref().ok().unwrap().content, Identifier("a".to_string()));
+	assert_eq!(lex.peek().unwrap().as_ref().ok().unwrap().content, Identifier("a".to_string()));
+	assert_eq!(lex.peek_n(1).unwrap().as_ref().ok().unwrap().content, Identifier("b".to_string()));
+	// peeked tokens are still handed out by `next`, in order, exactly once
+	assert_eq!(lex.next().unwrap().ok().unwrap().content, Identifier("a".to_string()));
+	assert_eq!(lex.next().unwrap().ok().unwrap().content, Identifier("b".to_string()));
+	assert_eq!(lex.next().unwrap().ok().unwrap().content, Identifier("c".to_string()));
+	assert!(lex.next().is_none());
+}
+
+#[test]
+fn lex_tolerates_errors() {
+	use TokenContent::*;
+	// a malformed escape is flagged on its own token, but lexing continues afterward instead
+	// of aborting the whole stream the way the IO-backed `Lexer` would
+	let tokens: Vec<RawToken> = StrLexer::new(r"'\q' + 1").collect();
+	assert_eq!(tokens.len(), 3);
+	assert_eq!(tokens[0].error, Some(TokenError::UnknownEscape));
+	assert_eq!(tokens[1].content, Operator("+".to_string()));
+	assert_eq!(tokens[1].error, None);
+	assert_eq!(tokens[2].content, IntLiteral { value: "1".to_string(), base: 10, suffix: None });
+	assert_eq!(tokens[2].error, None);
+}
+
+#[test]
+fn lex_operators_and_delimiters() {
+	use TokenContent::*;
+	use Delimiter::*;
+	assert_eq!(lex_str_contents("<<="), vec!(Operator("<<=".to_string())));
+	assert_eq!(lex_str_contents("<<"), vec!(Operator("<<".to_string())));
+	assert_eq!(lex_str_contents("<"), vec!(Operator("<".to_string())));
+	assert_eq!(lex_str_contents("..="), vec!(Operator("..=".to_string())));
+	assert_eq!(lex_str_contents(".."), vec!(Operator("..".to_string())));
+	assert_eq!(lex_str_contents("..."), vec!(Operator("...".to_string())));
+	assert_eq!(lex_str_contents("."), vec!(Operator(".".to_string())));
+	assert_eq!(lex_str_contents("()[]{}"), vec!(
+		DelimOpen(Parenthesis), DelimClose(Parenthesis),
+		DelimOpen(Bracket), DelimClose(Bracket),
+		DelimOpen(Brace), DelimClose(Brace)));
+	// unrecognized input still falls back to `Other`
+	assert_eq!(lex_str_contents("`"), vec!(Other('`')));
+}
+
+#[test]
+fn lex_escapes() {
+	use TokenContent::*;
+	assert_eq!(lex_str_contents(r"'\n' '\r' '\t' '\\' '\'' '\0'"), vec!(
+		Char('\n'), Char('\r'), Char('\t'), Char('\\'), Char('\''), Char('\0')));
+	assert_eq!(lex_str_contents(r"'\x41'"), vec!(Char('A')));
+	assert_eq!(lex_str_contents(r"'\u{48}'"), vec!(Char('H')));
+	// a backslash immediately followed by a newline is a line continuation: it and any
+	// leading whitespace on the next line contribute no character to the string
+	let mut tokens = StrLexer::new("\"a\\\n    b\"");
+	let tok = tokens.next().unwrap();
+	assert_eq!(tok.content, StringLiteral("ab".to_string()));
+	assert_eq!(tok.error, None);
+	// a `\u{...}` value outside the unicode scalar value range is flagged, not a hard error
+	let mut tokens = StrLexer::new(r"'\u{110000}'");
+	let tok = tokens.next().unwrap();
+	assert_eq!(tok.error, Some(TokenError::BadUnicodeEscape));
+}
+
+#[test]
+fn lex_comments() {
+	use TokenContent::*;
+	assert_eq!(lex_str_contents("/* /* */ still inside */ x"), vec!(
+		BlockComment(" /* */ still inside ".to_string()),
+		Identifier("x".to_string())));
+	assert_eq!(lex_str_contents("/// outer doc"), vec!(
+		DocComment { text: " outer doc".to_string(), inner: false }));
+	assert_eq!(lex_str_contents("//! inner doc"), vec!(
+		DocComment { text: " inner doc".to_string(), inner: true }));
+	assert_eq!(lex_str_contents("/** outer doc */"), vec!(
+		DocComment { text: " outer doc ".to_string(), inner: false }));
+	assert_eq!(lex_str_contents("/*! inner doc */"), vec!(
+		DocComment { text: " inner doc ".to_string(), inner: true }));
+	// four or more slashes, and an empty block comment, are plain comments, not doc comments
+	assert_eq!(lex_str_contents("//// not a doc comment"), vec!(
+		LineComment("// not a doc comment".to_string())));
+	assert_eq!(lex_str_contents("/**/"), vec!(BlockComment(String::new())));
+}
+
+#[test]
+fn lex_raw_and_byte_literals() {
+	use TokenContent::*;
+	assert_eq!(lex_str_contents(r#"r"plain""#), vec!(
+		RawStringLiteral { text: "plain".to_string(), hashes: 0 }));
+	// with hashes == 0, the first '"' closes the string, even though '#' follows it
+	assert_eq!(lex_str_contents(r##"r"a"#"##), vec!(
+		RawStringLiteral { text: "a".to_string(), hashes: 0 },
+		Operator("#".to_string())));
+	assert_eq!(lex_str_contents(r##"r#"has "one" quote"#"##), vec!(
+		RawStringLiteral { text: "has \"one\" quote".to_string(), hashes: 1 }));
+	assert_eq!(lex_str_contents(r#"b"bytes""#), vec!(
+		ByteStringLiteral(b"bytes".to_vec())));
+	assert_eq!(lex_str_contents(r#"b'x'"#), vec!(ByteLiteral(b'x')));
+	assert_eq!(lex_str_contents(r##"br#"raw bytes"#"##), vec!(
+		RawByteStringLiteral { text: b"raw bytes".to_vec(), hashes: 1 }));
+	// 'r' and 'b' are also identifier starts; in particular an identifier whose second
+	// character happens to be 'r' (like `break`) must not be misdiverted into the
+	// br-prefixed literal parser, which needs a second character of lookahead to tell them apart
+	assert_eq!(lex_str_contents("break bring brace broadcast"), vec!(
+		Identifier("break".to_string()),
+		Identifier("bring".to_string()),
+		Identifier("brace".to_string()),
+		Identifier("broadcast".to_string())));
+	// byte/byte-string/raw-byte-string literals are only ever ASCII; a non-ASCII source
+	// character must be flagged rather than silently truncated by the `as u8` cast
+	let tok = StrLexer::new("b'\u{3bb}'").next().unwrap();
+	assert_eq!(tok.error, Some(TokenError::UnknownEscape));
+	let tok = StrLexer::new("b\"caf\u{e9}\"").next().unwrap();
+	assert_eq!(tok.error, Some(TokenError::UnknownEscape));
+	let tok = StrLexer::new("br\"caf\u{e9}\"").next().unwrap();
+	assert_eq!(tok.error, Some(TokenError::UnknownEscape));
+}
+
+#[test]
+fn lex_numbers() {
+	use TokenContent::*;
+	assert_eq!(lex_str_contents("1_000"), vec!(
+		IntLiteral { value: "1000".to_string(), base: 10, suffix: None }));
+	assert_eq!(lex_str_contents("0xFF"), vec!(
+		IntLiteral { value: "FF".to_string(), base: 16, suffix: None }));
+	assert_eq!(lex_str_contents("0b1010"), vec!(
+		IntLiteral { value: "1010".to_string(), base: 2, suffix: None }));
+	assert_eq!(lex_str_contents("42u64"), vec!(
+		IntLiteral { value: "42".to_string(), base: 10, suffix: Some("u64".to_string()) }));
+	assert_eq!(lex_str_contents("3.14e-2"), vec!(
+		FloatLiteral { value: "3.14e-2".to_string(), suffix: None }));
+	assert_eq!(lex_str_contents("1f32"), vec!(
+		FloatLiteral { value: "1".to_string(), suffix: Some("f32".to_string()) }));
+	// `e10` right after the digits is an exponent, not a suffix
+	assert_eq!(lex_str_contents("1e10"), vec!(
+		FloatLiteral { value: "1e10".to_string(), suffix: None }));
+	// a `.` not followed by a digit is a method call, not a float continuation
+	assert_eq!(lex_str_contents("1.foo()"), vec!(
+		IntLiteral { value: "1".to_string(), base: 10, suffix: None },
+		Operator(".".to_string()),
+		Identifier("foo".to_string()),
+		DelimOpen(Delimiter::Parenthesis),
+		DelimClose(Delimiter::Parenthesis)));
+}
+
+#[test]
+fn lex_tracks_lines_across_multiline_tokens() {
+	use TokenContent::*;
+	let line_start = if cfg!(lines_start_at_zero) { 0 } else { 1 };
+	// a multi-line block comment (chunk0-3) or raw string (chunk0-2) must keep `line`
+	// accurate for whatever comes after it, the same way multi-line whitespace already does
+	let lex = match Lexer::new(
+		std::io::BufferedReader::new(
+			std::io::MemReader::new(std::vec::as_vec(b"/* line 1\nline 2\nline 3 */\nx").clone()))) {
+		Ok(x) => x,
+		Err(e) => panic!("{}", e)
+	};
+	let tokens: Vec<Token> = lex.map(|t| t.ok().unwrap()).collect();
+	assert_eq!(tokens.len(), 1);
+	assert_eq!(tokens[0].content, Identifier("x".to_string()));
+	assert_eq!(tokens[0].line, line_start + 3);
+	assert_eq!(tokens[0].start, 1);
+
+	let lex = match Lexer::new(
+		std::io::BufferedReader::new(
+			std::io::MemReader::new(std::vec::as_vec(b"r\"line 1\nline 2\"\ny").clone()))) {
+		Ok(x) => x,
+		Err(e) => panic!("{}", e)
+	};
+	let tokens: Vec<Token> = lex.map(|t| t.ok().unwrap()).collect();
+	assert_eq!(tokens.len(), 2);
+	assert_eq!(tokens[1].content, Identifier("y".to_string()));
+	assert_eq!(tokens[1].line, line_start + 2);
+	assert_eq!(tokens[1].start, 1);
 }
 
 #[test]
 fn lex_empty() {
+	use TokenContent::*;
+	use Delimiter::*;
 	let line_start = if cfg!(lines_start_at_zero) { 0 } else { 1 };
 	let lex = match Lexer::new(
 		std::io::BufferedReader::new(